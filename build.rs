@@ -0,0 +1,40 @@
+use std::env;
+use std::process::Command;
+
+/// Run a command and return its trimmed standard output, if it succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Expose build information to the `version` command.
+fn main() {
+    let commit = output("git", &["rev-parse", "--short", "HEAD"]);
+    println!(
+        "cargo:rustc-env=KAPS_GIT_COMMIT={}",
+        commit.unwrap_or_else(|| String::from("unknown"))
+    );
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    println!(
+        "cargo:rustc-env=KAPS_RUSTC_VERSION={}",
+        output(&rustc, &["--version"]).unwrap_or_else(|| String::from("unknown"))
+    );
+
+    // Cargo exposes every enabled feature as a `CARGO_FEATURE_<NAME>` variable.
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<String>>();
+    features.sort();
+    println!("cargo:rustc-env=KAPS_FEATURES={}", features.join(","));
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -1,34 +1,268 @@
+use std::collections::HashSet;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+
 use oci_spec::runtime::Process;
 
+/// The value displayed in place of a redacted variable.
+const REDACTED_VALUE: &str = "****";
+
 /// Implementation of the container environment.
+///
+/// The container only receives the variables of its specification and the ones
+/// explicitly set or inherited from the host: the host environment is never passed as a whole.
+///
+/// Variables are kept as `OsString`, as the host ones are not necessarily valid unicode.
 #[derive(Default)]
 pub struct Environment {
-    vars: Vec<(String, String)>,
+    vars: Vec<(OsString, OsString)>,
+    /// The variables whose value must never be displayed.
+    redacted: HashSet<String>,
 }
 
 impl Environment {
     /// Get the environment variables.
-    /// It converts the `Vec<(String, String)>` into a `Vec<(&str, &str)>` for `unshare` API compatibility.
-    pub fn get(&self) -> Vec<(&str, &str)> {
+    /// It converts the `Vec<(OsString, OsString)>` into a `Vec<(&OsStr, &OsStr)>` for `unshare` API compatibility.
+    pub fn get(&self) -> Vec<(&OsStr, &OsStr)> {
         self.vars
             .iter()
             .map(|(key, value)| (key.as_ref(), value.as_ref()))
             .collect()
     }
+
+    /// Set a variable, replacing its previous value if it is already defined.
+    pub fn set<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        match self.vars.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_os_string(),
+            None => self.vars.push((key.to_os_string(), value.to_os_string())),
+        }
+    }
+
+    /// Set a variable from a `KEY=VALUE` string, replacing its previous value if it is
+    /// already defined. Values may contain `=`, only the first one separates the key from the value.
+    pub fn set_var(&mut self, var: &str) -> crate::Result<()> {
+        match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                self.set(key, value);
+                Ok(())
+            }
+            _ => Err(crate::Error::InvalidEnv(var.to_string())),
+        }
+    }
+
+    /// Pass a variable of the host environment through to the container, if it is defined.
+    pub fn inherit(&mut self, key: &str) {
+        if let Some(value) = env::var_os(key) {
+            self.set(key, value);
+        }
+    }
+
+    /// Pass the variables of the host environment whose name matches a glob pattern
+    /// through to the container. `*` matches any sequence of characters, `?` a single one.
+    pub fn inherit_matching(&mut self, pattern: &str) {
+        // `env::vars` would panic on a non-unicode variable, rather than passing it through.
+        for (key, value) in env::vars_os() {
+            if glob_match(pattern.as_bytes(), key.as_bytes()) {
+                self.set(key, value);
+            }
+        }
+    }
+
+    /// Never display the value of a variable.
+    /// The real value is still given to the container process.
+    pub fn redact(&mut self, key: &str) {
+        self.redacted.insert(key.to_string());
+    }
+}
+
+impl fmt::Debug for Environment {
+    /// Format the environment, replacing the value of the redacted variables.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.vars.iter().map(|(key, value)| {
+                let key = key.to_string_lossy();
+                if self.redacted.contains(key.as_ref()) {
+                    format!("{}={}", key, REDACTED_VALUE)
+                } else {
+                    format!("{}={}", key, value.to_string_lossy())
+                }
+            }))
+            .finish()
+    }
 }
 
-impl From<&Option<Process>> for Environment {
-    fn from(process: &Option<Process>) -> Self {
-        let mut vars = Vec::<(String, String)>::new();
+impl TryFrom<&Option<Process>> for Environment {
+    type Error = crate::Error;
+
+    /// Build the environment of a specification, refusing a variable which is not `KEY=VALUE`.
+    fn try_from(process: &Option<Process>) -> crate::Result<Self> {
+        let mut environment = Environment::default();
         if let Some(process) = process {
             if let Some(env) = process.env() {
                 for var in env {
-                    let key_value = var.split('=').collect::<Vec<&str>>();
-                    vars.push((key_value[0].to_string(), key_value[1].to_string()));
+                    environment.set_var(var)?;
                 }
             }
         }
 
-        Environment { vars }
+        Ok(environment)
+    }
+}
+
+/// Check if a name matches a glob pattern made of literal characters, `*` and `?`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::ffi::OsStringExt;
+
+    fn spec_environment(env: &[&str]) -> crate::Result<Environment> {
+        let process: Process = serde_json::from_value(json!({
+            "cwd": "/",
+            "user": { "uid": 0, "gid": 0 },
+            "env": env
+        }))
+        .unwrap();
+
+        Environment::try_from(&Some(process))
+    }
+
+    fn value<'a>(environment: &'a Environment, key: &str) -> Option<&'a OsStr> {
+        environment
+            .get()
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    #[test]
+    fn spec_variables() {
+        let environment = spec_environment(&["PATH=/bin", "EMPTY=", "OPTS=a=b"]).unwrap();
+
+        assert_eq!(value(&environment, "PATH").unwrap(), "/bin");
+        assert_eq!(value(&environment, "EMPTY").unwrap(), "");
+        assert_eq!(value(&environment, "OPTS").unwrap(), "a=b");
+    }
+
+    #[test]
+    fn invalid_spec_variables() {
+        for var in ["FOO", "=bar", ""] {
+            assert!(
+                matches!(spec_environment(&[var]), Err(crate::Error::InvalidEnv(v)) if v == var),
+                "{} should be rejected",
+                var
+            );
+        }
+    }
+
+    #[test]
+    fn set_overrides_spec_variables() {
+        let mut environment = spec_environment(&["MODE=spec", "PATH=/bin"]).unwrap();
+        environment.set_var("MODE=cli").unwrap();
+
+        // The variable keeps its position, and is not duplicated.
+        assert_eq!(format!("{:?}", environment), r#"["MODE=cli", "PATH=/bin"]"#);
+    }
+
+    #[test]
+    fn inherit() {
+        env::set_var("KAPS_TEST_INHERIT", "host");
+        let mut environment = spec_environment(&["KAPS_TEST_INHERIT=spec"]).unwrap();
+
+        environment.inherit("KAPS_TEST_INHERIT");
+        environment.inherit("KAPS_TEST_INHERIT_UNDEFINED");
+
+        assert_eq!(value(&environment, "KAPS_TEST_INHERIT").unwrap(), "host");
+        assert!(value(&environment, "KAPS_TEST_INHERIT_UNDEFINED").is_none());
+
+        // A variable set explicitly takes precedence over the inherited one.
+        environment.set_var("KAPS_TEST_INHERIT=cli").unwrap();
+        assert_eq!(value(&environment, "KAPS_TEST_INHERIT").unwrap(), "cli");
+    }
+
+    #[test]
+    fn inherit_matching() {
+        env::set_var("KAPS_TEST_MATCH_ONE", "1");
+        env::set_var("KAPS_TEST_MATCH_TWO", "2");
+        env::set_var("KAPS_TEST_NO_MATCH", "3");
+        let mut environment = Environment::default();
+
+        environment.inherit_matching("KAPS_TEST_MATCH_*");
+
+        assert_eq!(value(&environment, "KAPS_TEST_MATCH_ONE").unwrap(), "1");
+        assert_eq!(value(&environment, "KAPS_TEST_MATCH_TWO").unwrap(), "2");
+        assert!(value(&environment, "KAPS_TEST_NO_MATCH").is_none());
+    }
+
+    #[test]
+    fn inherit_non_unicode_values() {
+        let non_unicode = OsString::from_vec(vec![b'a', 0xff, b'b']);
+        env::set_var("KAPS_TEST_NON_UNICODE", &non_unicode);
+        let mut environment = Environment::default();
+
+        environment.inherit_matching("KAPS_TEST_NON_UNI*");
+        assert_eq!(
+            value(&environment, "KAPS_TEST_NON_UNICODE").unwrap(),
+            non_unicode
+        );
+
+        let mut environment = Environment::default();
+        environment.inherit("KAPS_TEST_NON_UNICODE");
+        assert_eq!(
+            value(&environment, "KAPS_TEST_NON_UNICODE").unwrap(),
+            non_unicode
+        );
+    }
+
+    #[test]
+    fn redacted_values_are_never_displayed() {
+        let mut environment = spec_environment(&["TOKEN=s3cr3t", "USER=kaps"]).unwrap();
+        environment.redact("TOKEN");
+
+        let debug = format!("{:?}", environment);
+        assert!(!debug.contains("s3cr3t"));
+        assert_eq!(debug, r#"["TOKEN=****", "USER=kaps"]"#);
+
+        // The real value is still given to the container, even when set after the redaction.
+        environment.set_var("TOKEN=n3w").unwrap();
+        assert_eq!(value(&environment, "TOKEN").unwrap(), "n3w");
+        assert!(!format!("{:#?}", environment).contains("n3w"));
+    }
+
+    #[test]
+    fn glob() {
+        let matches = |pattern: &str, name: &str| glob_match(pattern.as_bytes(), name.as_bytes());
+
+        assert!(matches("PATH", "PATH"));
+        assert!(!matches("PATH", "PATHS"));
+        assert!(!matches("PATH", "path"));
+        assert!(matches("*", ""));
+        assert!(matches("*", "ANYTHING"));
+        assert!(matches("LC_*", "LC_ALL"));
+        assert!(matches("LC_*", "LC_"));
+        assert!(!matches("LC_*", "LANG"));
+        assert!(matches("*_PROXY", "HTTPS_PROXY"));
+        assert!(matches("A*B*C", "AxxBxxC"));
+        assert!(!matches("A*B*C", "AxxBxx"));
+        assert!(matches("TERM?", "TERM2"));
+        assert!(!matches("TERM?", "TERM"));
+        assert!(!matches("TERM?", "TERM22"));
+        assert!(matches("?*", "X"));
+        assert!(!matches("?*", ""));
     }
 }
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::host::{self, CgroupVersion};
+use crate::namespaces::SUPPORTED_NAMESPACES;
+
+/// The oldest version of the OCI runtime specification supported by our container.
+const OCI_VERSION_MIN: &str = "1.0.0";
+/// The annotation reporting the cgroup version detected on the host.
+const CGROUP_VERSION_ANNOTATION: &str = "io.github.virt-do.kaps.cgroup.version";
+
+/// Implementation of the OCI runtime `features` structure.
+///
+/// It reports what our container implements, and is serialized to the
+/// [format](https://github.com/opencontainers/runtime-spec/blob/main/features.md)
+/// expected by higher-level container managers.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Features {
+    oci_version_min: String,
+    oci_version_max: String,
+    /// No hook is run by our container.
+    hooks: Vec<String>,
+    linux: Linux,
+    annotations: BTreeMap<String, String>,
+}
+
+/// The Linux specific features.
+#[derive(Serialize)]
+struct Linux {
+    namespaces: Vec<String>,
+    cgroup: Cgroup,
+    seccomp: Enabled,
+    apparmor: Enabled,
+    selinux: Enabled,
+}
+
+/// The cgroup managers implemented by our container.
+#[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Cgroup {
+    v1: bool,
+    v2: bool,
+    systemd: bool,
+    systemd_user: bool,
+}
+
+/// Whether a security module is implemented by our container.
+#[derive(Default, Serialize)]
+struct Enabled {
+    enabled: bool,
+}
+
+impl Features {
+    /// Build the features report, detecting the host specific information.
+    pub fn detect() -> Self {
+        Self::new(host::cgroup_version())
+    }
+
+    /// Build the features report of a host using the given cgroup version, if any.
+    fn new(cgroup_version: Option<CgroupVersion>) -> Self {
+        let mut annotations = BTreeMap::new();
+        if let Some(version) = cgroup_version {
+            annotations.insert(CGROUP_VERSION_ANNOTATION.to_string(), version.to_string());
+        }
+
+        Features {
+            oci_version_min: OCI_VERSION_MIN.to_string(),
+            oci_version_max: crate::OCI_VERSION.to_string(),
+            hooks: vec![],
+            linux: Linux {
+                namespaces: SUPPORTED_NAMESPACES
+                    .iter()
+                    .map(|(name, _)| name.to_string())
+                    .collect(),
+                cgroup: Cgroup::default(),
+                seccomp: Enabled::default(),
+                apparmor: Enabled::default(),
+                selinux: Enabled::default(),
+            },
+            annotations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_shape() {
+        let features = serde_json::to_value(Features::new(Some(CgroupVersion::V2))).unwrap();
+
+        assert_eq!(
+            features,
+            json!({
+                "ociVersionMin": "1.0.0",
+                "ociVersionMax": crate::OCI_VERSION,
+                "hooks": [],
+                "linux": {
+                    "namespaces": ["cgroup", "ipc", "mount", "network", "pid", "uts"],
+                    "cgroup": { "v1": false, "v2": false, "systemd": false, "systemdUser": false },
+                    "seccomp": { "enabled": false },
+                    "apparmor": { "enabled": false },
+                    "selinux": { "enabled": false }
+                },
+                "annotations": { "io.github.virt-do.kaps.cgroup.version": "v2" }
+            })
+        );
+    }
+
+    #[test]
+    fn no_cgroup_annotation_without_cgroups() {
+        let features = serde_json::to_value(Features::new(None)).unwrap();
+
+        assert_eq!(features["annotations"], json!({}));
+    }
+}
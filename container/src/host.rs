@@ -0,0 +1,251 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// The mount table of the current process.
+const PROC_MOUNTS: &str = "/proc/self/mounts";
+/// The filesystems supported by the kernel.
+const PROC_FILESYSTEMS: &str = "/proc/filesystems";
+/// The status of the current process, including its capabilities.
+const PROC_STATUS: &str = "/proc/self/status";
+/// The maximum number of user namespaces that can be created.
+const PROC_MAX_USER_NAMESPACES: &str = "/proc/sys/user/max_user_namespaces";
+/// The bit of `CAP_SYS_ADMIN` in a capability set.
+const CAP_SYS_ADMIN: u32 = 21;
+/// The location where the cgroup hierarchies are mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The cgroup version used by the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgroupVersion {
+    /// Legacy hierarchies only.
+    V1,
+    /// Unified hierarchy only.
+    V2,
+    /// Legacy hierarchies, with the unified hierarchy mounted beside them.
+    Hybrid,
+}
+
+impl fmt::Display for CgroupVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CgroupVersion::V1 => write!(f, "v1"),
+            CgroupVersion::V2 => write!(f, "v2"),
+            CgroupVersion::Hybrid => write!(f, "hybrid"),
+        }
+    }
+}
+
+/// Detect the cgroup version used by the host from its mount table.
+/// It returns `None` if no cgroup filesystem is mounted.
+pub fn cgroup_version() -> Option<CgroupVersion> {
+    fs::read_to_string(PROC_MOUNTS)
+        .ok()
+        .and_then(|mounts| cgroup_version_from_mounts(&mounts))
+}
+
+/// Detect the cgroup version from the content of a `/proc/mounts` like file.
+pub fn cgroup_version_from_mounts(mounts: &str) -> Option<CgroupVersion> {
+    let mut v1 = false;
+    let mut v2 = false;
+    let mut unified_root = false;
+
+    for line in mounts.lines() {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 3 {
+            continue;
+        }
+
+        match fields[2] {
+            "cgroup" => v1 = true,
+            "cgroup2" => {
+                v2 = true;
+                unified_root |= fields[1] == CGROUP_ROOT;
+            }
+            _ => {}
+        }
+    }
+
+    match (v1, v2) {
+        _ if unified_root => Some(CgroupVersion::V2),
+        (true, true) => Some(CgroupVersion::Hybrid),
+        (true, false) => Some(CgroupVersion::V1),
+        (false, true) => Some(CgroupVersion::V2),
+        (false, false) => None,
+    }
+}
+
+/// Check if the cgroup filesystems are mounted read-write.
+pub fn cgroup_writable() -> bool {
+    fs::read_to_string(PROC_MOUNTS).is_ok_and(|mounts| cgroup_writable_from_mounts(&mounts))
+}
+
+/// Check if the cgroup filesystems of a `/proc/mounts` like file are mounted read-write.
+/// It returns `false` if no cgroup filesystem is mounted.
+pub fn cgroup_writable_from_mounts(mounts: &str) -> bool {
+    let options = mounts
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .filter(|fields| fields.len() >= 4 && fields[2].starts_with("cgroup"))
+        .map(|fields| fields[3].split(',').any(|option| option == "rw"))
+        .collect::<Vec<bool>>();
+
+    !options.is_empty() && options.into_iter().all(|rw| rw)
+}
+
+/// Check if the kernel supports overlayfs.
+pub fn overlayfs_supported() -> bool {
+    fs::read_to_string(PROC_FILESYSTEMS)
+        .is_ok_and(|filesystems| filesystem_supported(&filesystems, "overlay"))
+}
+
+/// Check if a filesystem is listed in a `/proc/filesystems` like file.
+pub fn filesystem_supported(filesystems: &str, name: &str) -> bool {
+    filesystems
+        .lines()
+        .any(|line| line.split_whitespace().last() == Some(name))
+}
+
+/// Check if the current process has `CAP_SYS_ADMIN` in its effective capabilities.
+pub fn has_cap_sys_admin() -> bool {
+    fs::read_to_string(PROC_STATUS)
+        .ok()
+        .and_then(|status| effective_capabilities(&status))
+        .is_some_and(|caps| caps & (1 << CAP_SYS_ADMIN) != 0)
+}
+
+/// Get the effective capability set (`CapEff`) from a `/proc/<pid>/status` like file.
+pub fn effective_capabilities(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+}
+
+/// Check if user namespaces can be created on the host.
+pub fn user_namespaces_available() -> bool {
+    fs::read_to_string(PROC_MAX_USER_NAMESPACES).is_ok_and(|max| user_namespaces_from_max(&max))
+}
+
+/// Check if the content of `/proc/sys/user/max_user_namespaces` allows user namespaces.
+pub fn user_namespaces_from_max(max: &str) -> bool {
+    max.trim().parse::<u64>().is_ok_and(|max| max > 0)
+}
+
+/// Find a binary in the directories of the `PATH` environment variable.
+pub fn find_binary(name: &str) -> Option<PathBuf> {
+    env::var_os("PATH").and_then(|path| find_binary_in(name, &path))
+}
+
+/// Find a binary in the directories of a `PATH` like value.
+pub fn find_binary_in(name: &str, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|binary| binary.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+tmpfs /sys/fs/cgroup tmpfs ro,nosuid,nodev,noexec,mode=755 0 0
+cgroup /sys/fs/cgroup/memory cgroup rw,nosuid,nodev,noexec,relatime,memory 0 0
+cgroup /sys/fs/cgroup/pids cgroup rw,nosuid,nodev,noexec,relatime,pids 0 0
+";
+
+    const V2_MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime,nsdelegate 0 0
+";
+
+    const HYBRID_MOUNTS: &str = "\
+tmpfs /sys/fs/cgroup tmpfs ro,nosuid,nodev,noexec,mode=755 0 0
+cgroup2 /sys/fs/cgroup/unified cgroup2 rw,nosuid,nodev,noexec,relatime 0 0
+cgroup /sys/fs/cgroup/memory cgroup rw,nosuid,nodev,noexec,relatime,memory 0 0
+";
+
+    #[test]
+    fn cgroup_versions() {
+        assert_eq!(
+            cgroup_version_from_mounts(V1_MOUNTS),
+            Some(CgroupVersion::V1)
+        );
+        assert_eq!(
+            cgroup_version_from_mounts(V2_MOUNTS),
+            Some(CgroupVersion::V2)
+        );
+        assert_eq!(
+            cgroup_version_from_mounts(HYBRID_MOUNTS),
+            Some(CgroupVersion::Hybrid)
+        );
+        assert_eq!(cgroup_version_from_mounts("proc /proc proc rw 0 0\n"), None);
+        assert_eq!(cgroup_version_from_mounts(""), None);
+    }
+
+    #[test]
+    fn cgroup_writable_mounts() {
+        assert!(cgroup_writable_from_mounts(V1_MOUNTS));
+        assert!(cgroup_writable_from_mounts(V2_MOUNTS));
+        assert!(!cgroup_writable_from_mounts(
+            "cgroup2 /sys/fs/cgroup cgroup2 ro,nosuid,nodev,noexec,relatime 0 0\n"
+        ));
+        // Every cgroup filesystem must be writable.
+        assert!(!cgroup_writable_from_mounts(&format!(
+            "{}cgroup /sys/fs/cgroup/cpu cgroup ro,relatime,cpu 0 0\n",
+            V1_MOUNTS
+        )));
+        assert!(!cgroup_writable_from_mounts(""));
+    }
+
+    #[test]
+    fn filesystems() {
+        let filesystems = "nodev\tsysfs\nnodev\ttmpfs\n\text4\nnodev\toverlay\n";
+
+        assert!(filesystem_supported(filesystems, "overlay"));
+        assert!(filesystem_supported(filesystems, "ext4"));
+        assert!(!filesystem_supported(filesystems, "nodev"));
+        assert!(!filesystem_supported(filesystems, "btrfs"));
+    }
+
+    #[test]
+    fn capabilities() {
+        let root = "Name:\tkaps\nCapInh:\t0000000000000000\nCapEff:\t000001ffffffffff\n";
+        let user = "Name:\tkaps\nCapEff:\t0000000000000000\n";
+
+        assert_eq!(effective_capabilities(root), Some(0x1ff_ffff_ffff));
+        assert_eq!(effective_capabilities(user), Some(0));
+        assert_eq!(effective_capabilities("Name:\tkaps\n"), None);
+        assert_eq!(effective_capabilities("CapEff:\tnot hex\n"), None);
+        assert_ne!(
+            effective_capabilities(root).unwrap() & (1 << CAP_SYS_ADMIN),
+            0
+        );
+    }
+
+    #[test]
+    fn user_namespaces() {
+        assert!(user_namespaces_from_max("63704\n"));
+        assert!(!user_namespaces_from_max("0\n"));
+        assert!(!user_namespaces_from_max(""));
+    }
+
+    #[test]
+    fn binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("umount"), "").unwrap();
+        fs::create_dir(bin.join("mount")).unwrap();
+
+        let path = env::join_paths([dir.path().join("missing"), bin.clone()]).unwrap();
+
+        assert_eq!(find_binary_in("umount", &path), Some(bin.join("umount")));
+        // A directory is not a binary.
+        assert_eq!(find_binary_in("mount", &path), None);
+        assert_eq!(find_binary_in("umount", OsStr::new("")), None);
+    }
+}
@@ -1,6 +1,10 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 use oci_spec::runtime::Spec;
+use serde_json::Value;
 
 use command::Command;
 use environment::Environment;
@@ -9,23 +13,197 @@ use namespaces::Namespaces;
 
 mod command;
 mod environment;
+pub mod features;
+pub mod host;
 mod mounts;
 mod namespaces;
+mod spec;
 
 /// Containers related errors
 #[derive(Debug)]
 pub enum Error {
-    OCISpecificationLoad(oci_spec::OciSpecError),
     OCIInvalidNamespace(oci_spec::runtime::LinuxNamespaceType),
     ContainerSpawnCommand(unshare::Error),
     ContainerWaitCommand(std::io::Error),
     ContainerExit(i32),
     Unmount(std::io::Error),
+    /// The path of a mount in the rootfs, and the host path it resolves to.
+    MountOutsideRootfs(PathBuf, PathBuf),
+    InvalidTmpfs(String),
+    InvalidBundle(PathBuf, std::io::Error),
+    /// The rootfs configured in the specification, the path it resolved to, and why it is invalid.
+    InvalidRootfs(PathBuf, PathBuf, std::io::Error),
+    /// The rootfs configured in the specification, and the path it resolved to.
+    RootfsNotFound(PathBuf, PathBuf),
+    RootfsNotADirectory(PathBuf, PathBuf),
+    /// The resolved rootfs, and the bundle it is outside of.
+    RootfsOutsideBundle(PathBuf, PathBuf),
+    OCISpecificationRead(std::io::Error),
+    OCISpecificationParse(serde_json::Error),
+    OCIUnknownFields(Vec<String>),
+    InvalidEnv(String),
+    /// The field of the specification which only applies to another platform than Linux.
+    UnsupportedPlatformSpec(String),
+    InvalidNamespaceOverride(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OCIInvalidNamespace(namespace) => {
+                write!(f, "unsupported namespace {:?}", namespace)
+            }
+            Error::ContainerSpawnCommand(e) => write!(f, "failed to spawn the container: {}", e),
+            Error::ContainerWaitCommand(e) => {
+                write!(f, "failed to wait for the container: {}", e)
+            }
+            Error::ContainerExit(code) => write!(f, "container exited with code {}", code),
+            Error::Unmount(e) => write!(f, "failed to unmount the container mounts: {}", e),
+            Error::MountOutsideRootfs(path, resolved) => write!(
+                f,
+                "refusing to unmount {}: it resolves to {}, outside of the rootfs",
+                path.display(),
+                resolved.display()
+            ),
+            Error::InvalidTmpfs(reason) => write!(f, "invalid tmpfs mount: {}", reason),
+            Error::InvalidBundle(bundle, e) => {
+                write!(f, "invalid bundle {}: {}", bundle.display(), e)
+            }
+            Error::InvalidRootfs(configured, resolved, e) => write!(
+                f,
+                "invalid rootfs {} (resolved to {}): {}",
+                configured.display(),
+                resolved.display(),
+                e
+            ),
+            Error::RootfsNotFound(configured, resolved) => write!(
+                f,
+                "rootfs {} (resolved to {}) does not exist",
+                configured.display(),
+                resolved.display()
+            ),
+            Error::RootfsNotADirectory(configured, resolved) => write!(
+                f,
+                "rootfs {} (resolved to {}) is not a directory",
+                configured.display(),
+                resolved.display()
+            ),
+            Error::RootfsOutsideBundle(rootfs, bundle) => write!(
+                f,
+                "rootfs {} is outside of the bundle {}",
+                rootfs.display(),
+                bundle.display()
+            ),
+            Error::OCISpecificationRead(e) => write!(f, "failed to read the specification: {}", e),
+            Error::OCISpecificationParse(e) => {
+                write!(f, "failed to parse the specification: {}", e)
+            }
+            Error::OCIUnknownFields(fields) => write!(
+                f,
+                "unknown fields in the specification: {}",
+                fields.join(", ")
+            ),
+            Error::UnsupportedPlatformSpec(field) => write!(
+                f,
+                "unsupported platform specification: `{}` does not apply to Linux containers",
+                field
+            ),
+            Error::InvalidNamespaceOverride(reason) => {
+                write!(f, "invalid namespace override: {}", reason)
+            }
+            Error::InvalidEnv(var) => {
+                write!(
+                    f,
+                    "invalid environment variable `{}`, expected KEY=VALUE",
+                    var
+                )
+            }
+        }
+    }
+}
+
+impl Error {
+    /// A stable, machine-readable code identifying the error.
+    ///
+    /// Codes are part of the kaps interface: they must never be renamed or reused.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::OCISpecificationRead(_) => "spec_read_failed",
+            Error::OCISpecificationParse(_) => "spec_parse_failed",
+            Error::OCIUnknownFields(_) => "spec_unknown_fields",
+            Error::OCIInvalidNamespace(_) => "invalid_namespace",
+            Error::UnsupportedPlatformSpec(_) => "unsupported_platform_spec",
+            Error::InvalidBundle(_, _) => "invalid_bundle",
+            Error::InvalidRootfs(_, _, _) => "invalid_rootfs",
+            Error::RootfsNotFound(_, _) => "rootfs_not_found",
+            Error::RootfsNotADirectory(_, _) => "rootfs_not_a_directory",
+            Error::RootfsOutsideBundle(_, _) => "rootfs_outside_bundle",
+            Error::InvalidTmpfs(_) => "invalid_tmpfs",
+            Error::InvalidEnv(_) => "invalid_env",
+            Error::InvalidNamespaceOverride(_) => "invalid_namespace_override",
+            Error::ContainerSpawnCommand(_) => "container_spawn_failed",
+            Error::ContainerWaitCommand(_) => "container_wait_failed",
+            Error::ContainerExit(_) => "container_exited",
+            Error::Unmount(_) => "unmount_failed",
+            Error::MountOutsideRootfs(_, _) => "mount_outside_rootfs",
+        }
+    }
+
+    /// The structured context of the error, e.g. the paths or the errno involved.
+    pub fn context(&self) -> Vec<(&'static str, String)> {
+        let errno = |e: &std::io::Error| e.raw_os_error().map(|errno| ("errno", errno.to_string()));
+
+        match self {
+            Error::OCISpecificationRead(e) | Error::ContainerWaitCommand(e) | Error::Unmount(e) => {
+                errno(e).into_iter().collect()
+            }
+            Error::OCIUnknownFields(fields) => vec![("fields", fields.join(","))],
+            Error::OCIInvalidNamespace(namespace) => {
+                vec![("namespace", format!("{:?}", namespace))]
+            }
+            Error::UnsupportedPlatformSpec(field) => vec![("field", field.clone())],
+            Error::InvalidBundle(bundle, e) => {
+                let mut context = vec![("bundle", bundle.display().to_string())];
+                context.extend(errno(e));
+                context
+            }
+            Error::InvalidRootfs(configured, resolved, e) => {
+                let mut context = vec![
+                    ("configured", configured.display().to_string()),
+                    ("rootfs", resolved.display().to_string()),
+                ];
+                context.extend(errno(e));
+                context
+            }
+            Error::RootfsNotFound(configured, resolved)
+            | Error::RootfsNotADirectory(configured, resolved) => vec![
+                ("configured", configured.display().to_string()),
+                ("rootfs", resolved.display().to_string()),
+            ],
+            Error::RootfsOutsideBundle(rootfs, bundle) => vec![
+                ("rootfs", rootfs.display().to_string()),
+                ("bundle", bundle.display().to_string()),
+            ],
+            Error::ContainerExit(code) => vec![("exit_code", code.to_string())],
+            Error::MountOutsideRootfs(path, resolved) => vec![
+                ("mount", path.display().to_string()),
+                ("resolved", resolved.display().to_string()),
+            ],
+            Error::OCISpecificationParse(_)
+            | Error::InvalidTmpfs(_)
+            | Error::InvalidEnv(_)
+            | Error::InvalidNamespaceOverride(_)
+            | Error::ContainerSpawnCommand(_) => vec![],
+        }
+    }
 }
 
 /// A common result type for our container module.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The version of the OCI runtime specification implemented by our container.
+pub const OCI_VERSION: &str = "1.0.2";
+
 /// Some OCI constants useful for our container implementation.
 const OCI_RUNTIME_SPEC_FILE: &str = "config.json";
 const OCI_RUNTIME_SPEC_ROOTFS: &str = "rootfs";
@@ -34,6 +212,11 @@ const OCI_RUNTIME_SPEC_ROOTFS: &str = "rootfs";
 /// create and run a container on the host.
 #[derive(Default)]
 pub struct Container {
+    /// The canonical path to the bundle of the container
+    bundle: PathBuf,
+    /// The specification of the container, typed and as raw JSON
+    spec: Spec,
+    raw_spec: Value,
     /// The path to the rootfs used by the container
     rootfs: PathBuf,
     /// The namespaces which will be given to the container
@@ -49,20 +232,14 @@ pub struct Container {
 impl Container {
     /// Build a new container with the bundle provided in parameters.
     pub fn new(bundle_path: &str) -> Result<Self> {
-        let bundle = PathBuf::from(bundle_path);
+        let bundle = PathBuf::from(bundle_path)
+            .canonicalize()
+            .map_err(|e| Error::InvalidBundle(PathBuf::from(bundle_path), e))?;
 
         // Load the specification from the file
-        let spec =
-            Spec::load(&bundle.join(OCI_RUNTIME_SPEC_FILE)).map_err(Error::OCISpecificationLoad)?;
+        let (spec, raw) = Self::load_spec(&bundle)?;
 
-        // Get the container rootfs from the OCI specification, and if not present, set to
-        // the default `bundle_path/rootfs`
-        let rootfs = spec
-            .root()
-            .as_ref()
-            .map_or(bundle.join(OCI_RUNTIME_SPEC_ROOTFS), |x| {
-                bundle.clone().join(x.path())
-            });
+        let rootfs = Self::rootfs(&bundle, &spec)?;
 
         // Get the container namespaces if the linux block is defined into the specification.
         let namespaces = spec
@@ -73,14 +250,154 @@ impl Container {
             });
 
         Ok(Container {
-            environment: Environment::from(spec.process()),
+            environment: Environment::try_from(spec.process())?,
             command: Command::from(spec.process()),
             namespaces,
             rootfs,
+            bundle,
+            spec,
+            raw_spec: raw,
             ..Default::default()
         })
     }
 
+    /// Load the OCI specification of a bundle.
+    ///
+    /// The specification is returned both typed and as raw JSON, as the typed specification
+    /// silently drops the fields it does not know about.
+    fn load_spec(bundle: &Path) -> Result<(Spec, Value)> {
+        let content =
+            fs::read(bundle.join(OCI_RUNTIME_SPEC_FILE)).map_err(Error::OCISpecificationRead)?;
+
+        let raw: Value = serde_json::from_slice(&content).map_err(Error::OCISpecificationParse)?;
+
+        // Fail fast on a specification written for another platform, rather than failing
+        // obscurely on the fields it lacks, e.g. the POSIX `process.user`, or later on the
+        // chroot or exec. It is checked before the typed parsing for that reason.
+        if let Some(field) = spec::other_platform_field(&raw) {
+            return Err(Error::UnsupportedPlatformSpec(field.to_string()));
+        }
+
+        let spec: Spec = serde_json::from_slice(&content).map_err(Error::OCISpecificationParse)?;
+
+        Ok((spec, raw))
+    }
+
+    /// Resolve the container rootfs from the OCI specification.
+    ///
+    /// As per the OCI specification, on POSIX platforms `root.path` is "either an absolute path
+    /// or a relative path to the bundle": an absolute path is used as-is, and is never joined
+    /// to the bundle. If `root` is not present, the rootfs defaults to `bundle_path/rootfs`.
+    ///
+    /// The rootfs is checked to exist up front, rather than failing later on the chroot.
+    /// Symbolic links and `..` components are resolved, so the returned path is the
+    /// directory the container will actually be chrooted into.
+    fn rootfs(bundle: &Path, spec: &Spec) -> Result<PathBuf> {
+        let configured = spec
+            .root()
+            .as_ref()
+            .map_or(PathBuf::from(OCI_RUNTIME_SPEC_ROOTFS), |root| {
+                root.path().clone()
+            });
+
+        let resolved = if configured.is_absolute() {
+            configured.clone()
+        } else {
+            bundle.join(&configured)
+        };
+
+        let rootfs = resolved.canonicalize().map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::RootfsNotFound(configured.clone(), resolved.clone()),
+            _ => Error::InvalidRootfs(configured.clone(), resolved.clone(), e),
+        })?;
+
+        if !rootfs.is_dir() {
+            return Err(Error::RootfsNotADirectory(configured, rootfs));
+        }
+
+        Ok(rootfs)
+    }
+
+    /// Refuse a rootfs located outside of the container bundle.
+    ///
+    /// This should be used when the bundle comes from an untrusted source, as its specification
+    /// could otherwise make the container run on top of any directory of the host.
+    pub fn confine_rootfs(&self) -> Result<()> {
+        if !self.rootfs.starts_with(&self.bundle) {
+            return Err(Error::RootfsOutsideBundle(
+                self.rootfs.clone(),
+                self.bundle.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Refuse a specification containing fields unknown to our container.
+    ///
+    /// Unknown fields are otherwise silently ignored, so a misspelled field makes the container
+    /// run with its default value instead. The specification the container was built from is
+    /// checked, and every unknown field is reported.
+    pub fn check_spec_fields(&self) -> Result<()> {
+        let unknown = spec::unknown_fields(&self.raw_spec, &self.spec)
+            .map_err(Error::OCISpecificationParse)?;
+        if !unknown.is_empty() {
+            return Err(Error::OCIUnknownFields(unknown));
+        }
+
+        Ok(())
+    }
+
+    /// Set an environment variable of the container from a `KEY=VALUE` string.
+    /// It overrides the value defined by the specification, if any.
+    pub fn set_env(&mut self, var: &str) -> Result<()> {
+        self.environment.set_var(var)
+    }
+
+    /// Pass an environment variable of the host through to the container.
+    pub fn inherit_env(&mut self, key: &str) {
+        self.environment.inherit(key);
+    }
+
+    /// Pass the environment variables of the host matching a glob pattern through to the container.
+    pub fn inherit_env_matching(&mut self, pattern: &str) {
+        self.environment.inherit_matching(pattern);
+    }
+
+    /// Never display the value of an environment variable of the container.
+    pub fn redact_env(&mut self, key: &str) {
+        self.environment.redact(key);
+    }
+
+    /// Adjust the namespaces requested by the specification: the `unshare` ones are added,
+    /// and the `share` ones are removed so that the container shares them with the host.
+    ///
+    /// It returns the namespaces the container now shares with the host, and which the
+    /// specification had requested.
+    ///
+    /// Example: `container.override_namespaces(&[], &[String::from("network")])` runs the
+    /// container in the host network namespace.
+    pub fn override_namespaces(
+        &mut self,
+        unshare: &[String],
+        share: &[String],
+    ) -> Result<Vec<String>> {
+        self.namespaces.apply_overrides(unshare, share)
+    }
+
+    /// Do not mount the default `/dev`, `/proc` and `/sys` mounts into the container.
+    /// Only the mounts explicitly added to it are mounted.
+    pub fn disable_default_mounts(&mut self) {
+        self.mounts.disable_defaults();
+    }
+
+    /// Mount a tmpfs into the container, described by a `<destination>[:<options>]` string.
+    ///
+    /// Example: `/run:size=16m,mode=755`
+    pub fn add_tmpfs(&mut self, tmpfs: &str) -> Result<()> {
+        self.mounts.add_tmpfs(tmpfs)
+    }
+
     /// Run the container.
     pub fn run(&self) -> Result<()> {
         let mounts = self.mounts.clone();
@@ -108,3 +425,220 @@ impl Container {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    /// Create a bundle holding a specification, with its `root.path` set if given.
+    fn bundle(dir: &Path, root: Option<&str>) {
+        let mut spec = json!({ "ociVersion": OCI_VERSION });
+        if let Some(path) = root {
+            spec["root"] = json!({ "path": path });
+        }
+
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(OCI_RUNTIME_SPEC_FILE), spec.to_string()).unwrap();
+    }
+
+    fn container(bundle: &Path) -> Result<Container> {
+        Container::new(bundle.to_str().unwrap())
+    }
+
+    /// Create a temporary directory, and get its canonical path.
+    fn tempdir() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().canonicalize().unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn default_rootfs() {
+        let (_dir, path) = tempdir();
+        bundle(&path, None);
+        fs::create_dir(path.join("rootfs")).unwrap();
+
+        let container = container(&path).unwrap();
+
+        assert_eq!(container.rootfs, path.join("rootfs"));
+        assert!(container.confine_rootfs().is_ok());
+    }
+
+    #[test]
+    fn relative_rootfs() {
+        let (_dir, path) = tempdir();
+        bundle(&path, Some("images/base"));
+        fs::create_dir_all(path.join("images/base")).unwrap();
+
+        let container = container(&path).unwrap();
+
+        assert_eq!(container.rootfs, path.join("images/base"));
+        assert!(container.confine_rootfs().is_ok());
+    }
+
+    #[test]
+    fn absolute_rootfs() {
+        let (_dir, path) = tempdir();
+        let (_rootfs_dir, rootfs) = tempdir();
+        bundle(&path, Some(rootfs.to_str().unwrap()));
+
+        let container = container(&path).unwrap();
+
+        // An absolute path is never joined to the bundle.
+        assert_eq!(container.rootfs, rootfs);
+        assert!(matches!(
+            container.confine_rootfs(),
+            Err(Error::RootfsOutsideBundle(r, b)) if r == rootfs && b == path
+        ));
+    }
+
+    #[test]
+    fn rootfs_escaping_the_bundle() {
+        let (_dir, path) = tempdir();
+        bundle(&path.join("bundle"), Some("../outside"));
+        fs::create_dir(path.join("outside")).unwrap();
+
+        let container = container(&path.join("bundle")).unwrap();
+
+        assert_eq!(container.rootfs, path.join("outside"));
+        assert!(matches!(
+            container.confine_rootfs(),
+            Err(Error::RootfsOutsideBundle(_, _))
+        ));
+    }
+
+    #[test]
+    fn symlinked_rootfs() {
+        let (_dir, path) = tempdir();
+        bundle(&path.join("bundle"), None);
+        fs::create_dir(path.join("outside")).unwrap();
+        symlink(path.join("outside"), path.join("bundle/rootfs")).unwrap();
+
+        let container = container(&path.join("bundle")).unwrap();
+
+        // The rootfs is the directory the container is actually chrooted into.
+        assert_eq!(container.rootfs, path.join("outside"));
+        assert!(matches!(
+            container.confine_rootfs(),
+            Err(Error::RootfsOutsideBundle(_, _))
+        ));
+    }
+
+    #[test]
+    fn symlinked_rootfs_inside_the_bundle() {
+        let (_dir, path) = tempdir();
+        bundle(&path, None);
+        fs::create_dir(path.join("base")).unwrap();
+        symlink("base", path.join("rootfs")).unwrap();
+
+        let container = container(&path).unwrap();
+
+        assert_eq!(container.rootfs, path.join("base"));
+        assert!(container.confine_rootfs().is_ok());
+    }
+
+    #[test]
+    fn missing_rootfs() {
+        let (_dir, path) = tempdir();
+        bundle(&path, Some("missing"));
+
+        let error = container(&path).err().unwrap();
+
+        assert!(matches!(
+            &error,
+            Error::RootfsNotFound(configured, resolved)
+                if configured == Path::new("missing") && resolved == &path.join("missing")
+        ));
+        assert_eq!(
+            error.context(),
+            [
+                ("configured", String::from("missing")),
+                ("rootfs", path.join("missing").display().to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_absolute_rootfs() {
+        let (_dir, path) = tempdir();
+        let (_rootfs_dir, rootfs) = tempdir();
+        let missing = rootfs.join("missing");
+        bundle(&path, Some(missing.to_str().unwrap()));
+
+        assert!(matches!(
+            container(&path),
+            Err(Error::RootfsNotFound(configured, resolved))
+                if configured == missing && resolved == missing
+        ));
+    }
+
+    #[test]
+    fn rootfs_not_a_directory() {
+        let (_dir, path) = tempdir();
+        bundle(&path, None);
+        fs::write(path.join("rootfs"), "").unwrap();
+
+        assert!(matches!(
+            container(&path),
+            Err(Error::RootfsNotADirectory(configured, resolved))
+                if configured == Path::new("rootfs") && resolved == path.join("rootfs")
+        ));
+    }
+
+    #[test]
+    fn unknown_spec_fields() {
+        let (_dir, path) = tempdir();
+        fs::create_dir(path.join("rootfs")).unwrap();
+        let spec = json!({
+            "ociVersion": OCI_VERSION,
+            "hostnmae": "kaps",
+            "root": { "path": "rootfs", "readOnly": true }
+        });
+        fs::write(path.join(OCI_RUNTIME_SPEC_FILE), spec.to_string()).unwrap();
+
+        let error = container(&path).unwrap().check_spec_fields().err().unwrap();
+
+        assert!(
+            matches!(&error, Error::OCIUnknownFields(fields) if fields == &["hostnmae", "root.readOnly"])
+        );
+        assert_eq!(
+            error.to_string(),
+            "unknown fields in the specification: hostnmae, root.readOnly"
+        );
+        assert_eq!(error.code(), "spec_unknown_fields");
+    }
+
+    #[test]
+    fn spec_fields_are_checked_on_the_loaded_spec() {
+        let (_dir, path) = tempdir();
+        bundle(&path, None);
+        fs::create_dir(path.join("rootfs")).unwrap();
+
+        let container = container(&path).unwrap();
+        // Changing the specification once the container is built has no effect on it.
+        fs::write(path.join(OCI_RUNTIME_SPEC_FILE), r#"{ "unknown": true }"#).unwrap();
+
+        assert!(container.check_spec_fields().is_ok());
+    }
+
+    #[test]
+    fn windows_spec() {
+        let (_dir, path) = tempdir();
+        fs::create_dir(path.join("rootfs")).unwrap();
+        // A Windows process has no POSIX `user`, which the typed specification requires.
+        let spec = json!({
+            "ociVersion": OCI_VERSION,
+            "process": { "cwd": "C:\\", "args": ["cmd.exe"] },
+            "windows": { "layerFolders": ["C:\\layers\\base"] }
+        });
+        fs::write(path.join(OCI_RUNTIME_SPEC_FILE), spec.to_string()).unwrap();
+
+        assert!(matches!(
+            container(&path),
+            Err(Error::UnsupportedPlatformSpec(field)) if field == "windows"
+        ));
+    }
+}
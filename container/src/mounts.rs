@@ -1,28 +1,180 @@
 use crate::Error;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
+/// Options accepted by the `--tmpfs` flag, in addition to `size` and `mode`.
+const TMPFS_FLAGS: [&str; 8] = [
+    "ro", "rw", "exec", "noexec", "suid", "nosuid", "dev", "nodev",
+];
+
 /// Implementation of the OCI `Mount`.
 #[derive(Clone)]
 struct Mount {
     typ: String,
     source: String,
     destination: String,
+    options: Vec<String>,
+}
+
+impl Mount {
+    /// Build a tmpfs mount from a `<destination>[:<options>]` string,
+    /// e.g. `/run:size=16m,mode=755,noexec`.
+    fn tmpfs(tmpfs: &str) -> crate::Result<Self> {
+        let (destination, options) = tmpfs.split_once(':').unwrap_or((tmpfs, ""));
+
+        if !destination.starts_with('/') {
+            return Err(Error::InvalidTmpfs(format!(
+                "destination `{}` must be an absolute path",
+                destination
+            )));
+        }
+
+        // A `..` component would make the destination escape the rootfs. Symbolic links
+        // can still do so, they are checked when cleaning up the mounts.
+        if Path::new(destination)
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(Error::InvalidTmpfs(format!(
+                "destination `{}` must not contain `..`",
+                destination
+            )));
+        }
+
+        // Mounting over the container root would hide its whole rootfs.
+        if !Path::new(destination)
+            .components()
+            .any(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(Error::InvalidTmpfs(String::from(
+                "destination cannot be the container root `/`",
+            )));
+        }
+
+        let options = options
+            .split(',')
+            .filter(|option| !option.is_empty())
+            .map(Self::parse_tmpfs_option)
+            .collect::<crate::Result<Vec<String>>>()?;
+
+        Ok(Mount {
+            typ: String::from("tmpfs"),
+            source: String::from("tmpfs"),
+            destination: destination.to_string(),
+            options,
+        })
+    }
+
+    /// Validate a single tmpfs option.
+    /// `size` accepts a number of bytes with an optional `k`, `m`, `g` or `%` suffix,
+    /// and `mode` an octal permission.
+    fn parse_tmpfs_option(option: &str) -> crate::Result<String> {
+        match option.split_once('=') {
+            Some(("size", size)) => {
+                let digits = size.trim_end_matches(|c| "kKmMgG%".contains(c));
+                if digits.is_empty()
+                    || size.len() - digits.len() > 1
+                    || digits.parse::<u64>().is_err()
+                {
+                    return Err(Error::InvalidTmpfs(format!(
+                        "invalid size `{}`, expected a number with an optional k, m, g or % suffix",
+                        size
+                    )));
+                }
+            }
+            Some(("mode", mode)) => {
+                if !matches!(u32::from_str_radix(mode, 8), Ok(mode) if mode <= 0o7777) {
+                    return Err(Error::InvalidTmpfs(format!(
+                        "invalid mode `{}`, expected an octal permission such as 1777",
+                        mode
+                    )));
+                }
+            }
+            None if TMPFS_FLAGS.contains(&option) => {}
+            _ => {
+                return Err(Error::InvalidTmpfs(format!(
+                    "unsupported option `{}`, expected size, mode or one of {}",
+                    option,
+                    TMPFS_FLAGS.join(", ")
+                )))
+            }
+        }
+
+        Ok(option.to_string())
+    }
 }
 
 #[derive(Clone)]
 pub struct Mounts {
+    /// Whether the default mounts are mounted before the other ones.
+    defaults: bool,
     vec: Vec<Mount>,
 }
 
 impl Mounts {
+    /// Returns the default mounts for a container.
+    /// Based on the OCI Specification
+    fn defaults() -> Vec<Mount> {
+        vec![
+            Mount {
+                typ: String::from("devtmpfs"),
+                source: String::from("dev"),
+                destination: String::from("/dev"),
+                options: vec![],
+            },
+            Mount {
+                typ: String::from("proc"),
+                source: String::from("proc"),
+                destination: String::from("/proc"),
+                options: vec![],
+            },
+            Mount {
+                typ: String::from("sysfs"),
+                source: String::from("sys"),
+                destination: String::from("/sys"),
+                options: vec![],
+            },
+        ]
+    }
+
+    /// Get every mount of the container, in the order they must be mounted.
+    fn list(&self) -> Vec<Mount> {
+        let mut list = if self.defaults {
+            Self::defaults()
+        } else {
+            vec![]
+        };
+        list.extend(self.vec.iter().cloned());
+
+        list
+    }
+
+    /// Do not mount the default mounts into the container.
+    pub fn disable_defaults(&mut self) {
+        self.defaults = false;
+    }
+
+    /// Add a tmpfs mount described by a `<destination>[:<options>]` string.
+    pub fn add_tmpfs(&mut self, tmpfs: &str) -> crate::Result<()> {
+        self.vec.push(Mount::tmpfs(tmpfs)?);
+        Ok(())
+    }
+
     /// Apply some mounts.
     /// This method should be called before the container process execution in order to prepare
     /// & mount every mounts defined for it.
     pub fn apply(mounts: &Mounts) -> Result<(), std::io::Error> {
-        for mount in &mounts.vec {
-            if let Some(code) = Command::new("mount")
-                .args(["-t", &mount.typ, &mount.source, &mount.destination])
+        for mount in mounts.list() {
+            std::fs::create_dir_all(&mount.destination)?;
+
+            let mut command = Command::new("mount");
+            command.args(["-t", &mount.typ]);
+            if !mount.options.is_empty() {
+                command.args(["-o", &mount.options.join(",")]);
+            }
+
+            if let Some(code) = command
+                .args([&mount.source, &mount.destination])
                 .status()?
                 .code()
             {
@@ -36,13 +188,24 @@ impl Mounts {
 
     /// Cleanup the mounts of a rootfs.
     /// This method should be called when a container has ended, to clean up the FS.
+    /// Mounts are unmounted in the reverse order, so that nested mounts go first.
+    ///
+    /// The cleanup runs on the host, where the symbolic links of the rootfs are resolved
+    /// against the host root: a mount whose path resolves outside of `rootfs`, e.g. through
+    /// an absolute `/var/run -> /run` link, is refused rather than unmounting a host path.
+    /// `rootfs` must be a canonical path.
     pub fn cleanup(&self, rootfs: PathBuf) -> Result<(), crate::Error> {
-        for mount in &self.vec {
+        for mount in self.list().iter().rev() {
             let mut path = rootfs.clone();
-            path.push(&mount.source);
+            path.push(mount.destination.trim_start_matches('/'));
+
+            let resolved = path.canonicalize().map_err(Error::Unmount)?;
+            if !resolved.starts_with(&rootfs) || resolved == rootfs {
+                return Err(Error::MountOutsideRootfs(path, resolved));
+            }
 
             if let Some(code) = Command::new("umount")
-                .args([path])
+                .args([resolved])
                 .status()
                 .map_err(Error::Unmount)?
                 .code()
@@ -60,27 +223,160 @@ impl Mounts {
 }
 
 impl Default for Mounts {
-    /// Returns the default mounts for a container.
-    /// Based on the OCI Specification
+    /// Returns a container mounts, starting with the default mounts.
     fn default() -> Self {
         Mounts {
-            vec: vec![
-                Mount {
-                    typ: String::from("devtmpfs"),
-                    source: String::from("dev"),
-                    destination: String::from("/dev"),
-                },
-                Mount {
-                    typ: String::from("proc"),
-                    source: String::from("proc"),
-                    destination: String::from("/proc"),
-                },
-                Mount {
-                    typ: String::from("sysfs"),
-                    source: String::from("sys"),
-                    destination: String::from("/sys"),
-                },
-            ],
+            defaults: true,
+            vec: vec![],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn tmpfs_without_options() {
+        let mount = Mount::tmpfs("/run").unwrap();
+
+        assert_eq!(mount.typ, "tmpfs");
+        assert_eq!(mount.source, "tmpfs");
+        assert_eq!(mount.destination, "/run");
+        assert!(mount.options.is_empty());
+    }
+
+    #[test]
+    fn tmpfs_with_options() {
+        let mount = Mount::tmpfs("/run:size=16m,mode=1777,noexec,nosuid").unwrap();
+
+        assert_eq!(mount.destination, "/run");
+        assert_eq!(mount.options, ["size=16m", "mode=1777", "noexec", "nosuid"]);
+    }
+
+    #[test]
+    fn tmpfs_valid_sizes() {
+        for size in ["1024", "64k", "16m", "1G", "50%"] {
+            let mount = Mount::tmpfs(&format!("/tmp:size={}", size)).unwrap();
+            assert_eq!(mount.options, [format!("size={}", size)]);
+        }
+    }
+
+    #[test]
+    fn tmpfs_invalid_sizes() {
+        for size in ["", "m", "16mb", "16t", "-1", "1.5m"] {
+            let tmpfs = format!("/tmp:size={}", size);
+            assert!(
+                matches!(Mount::tmpfs(&tmpfs), Err(Error::InvalidTmpfs(_))),
+                "{} should be rejected",
+                tmpfs
+            );
+        }
+    }
+
+    #[test]
+    fn tmpfs_modes() {
+        assert!(Mount::tmpfs("/tmp:mode=755").is_ok());
+        assert!(Mount::tmpfs("/tmp:mode=7777").is_ok());
+
+        for mode in ["", "789", "17777", "rwx"] {
+            let tmpfs = format!("/tmp:mode={}", mode);
+            assert!(
+                matches!(Mount::tmpfs(&tmpfs), Err(Error::InvalidTmpfs(_))),
+                "{} should be rejected",
+                tmpfs
+            );
+        }
+    }
+
+    #[test]
+    fn tmpfs_flags() {
+        for flag in TMPFS_FLAGS {
+            assert!(Mount::tmpfs(&format!("/tmp:{}", flag)).is_ok());
+        }
+
+        for option in ["exec=1", "uid=0", "bind", "size"] {
+            let tmpfs = format!("/tmp:{}", option);
+            assert!(
+                matches!(Mount::tmpfs(&tmpfs), Err(Error::InvalidTmpfs(_))),
+                "{} should be rejected",
+                tmpfs
+            );
+        }
+    }
+
+    #[test]
+    fn tmpfs_destinations() {
+        assert!(Mount::tmpfs("/dev/shm").is_ok());
+        // A trailing `:` means no options.
+        assert!(Mount::tmpfs("/run:").is_ok());
+
+        for tmpfs in [
+            "",
+            "run",
+            "./run",
+            "/",
+            "//",
+            "/.",
+            "/..",
+            "/../../../etc:size=1m",
+            "/run/../../etc",
+        ] {
+            assert!(
+                matches!(Mount::tmpfs(tmpfs), Err(Error::InvalidTmpfs(_))),
+                "{} should be rejected",
+                tmpfs
+            );
+        }
+    }
+
+    fn destinations(mounts: &Mounts) -> Vec<String> {
+        mounts
+            .list()
+            .into_iter()
+            .map(|mount| mount.destination)
+            .collect()
+    }
+
+    #[test]
+    fn list_starts_with_defaults() {
+        let mut mounts = Mounts::default();
+        mounts.add_tmpfs("/run").unwrap();
+
+        assert_eq!(destinations(&mounts), ["/dev", "/proc", "/sys", "/run"]);
+    }
+
+    #[test]
+    fn list_without_defaults() {
+        let mut mounts = Mounts::default();
+        mounts.disable_defaults();
+        assert!(mounts.list().is_empty());
+
+        mounts.add_tmpfs("/run").unwrap();
+        mounts.add_tmpfs("/tmp:size=1m").unwrap();
+
+        assert_eq!(destinations(&mounts), ["/run", "/tmp"]);
+    }
+
+    #[test]
+    fn cleanup_refuses_mounts_resolving_outside_the_rootfs() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let rootfs = rootfs.path().canonicalize().unwrap();
+        let host = tempfile::tempdir().unwrap();
+        fs::create_dir(rootfs.join("var")).unwrap();
+        // As shipped by many images, resolved against the host root once outside of the chroot.
+        symlink(host.path(), rootfs.join("var/run")).unwrap();
+
+        let mut mounts = Mounts::default();
+        mounts.disable_defaults();
+        mounts.add_tmpfs("/var/run").unwrap();
+
+        assert!(matches!(
+            mounts.cleanup(rootfs.clone()),
+            Err(Error::MountOutsideRootfs(path, resolved))
+                if path == rootfs.join("var/run") && resolved == host.path().canonicalize().unwrap()
+        ));
+    }
+}
@@ -2,6 +2,17 @@ use crate::Error;
 use oci_spec::runtime::{LinuxNamespace, LinuxNamespaceType};
 use unshare::Namespace;
 
+/// The namespaces a container can be placed into, along with their OCI name.
+/// The user namespace is not part of it yet, awaiting GUID and UID to be merged.
+pub const SUPPORTED_NAMESPACES: [(&str, LinuxNamespaceType); 6] = [
+    ("cgroup", LinuxNamespaceType::Cgroup),
+    ("ipc", LinuxNamespaceType::Ipc),
+    ("mount", LinuxNamespaceType::Mount),
+    ("network", LinuxNamespaceType::Network),
+    ("pid", LinuxNamespaceType::Pid),
+    ("uts", LinuxNamespaceType::Uts),
+];
+
 #[derive(Default)]
 pub struct Namespaces {
     vec: Vec<Namespace>,
@@ -13,6 +24,66 @@ impl Namespaces {
         &self.vec
     }
 
+    /// Adjust the namespaces computed from the specification.
+    ///
+    /// The namespaces of `unshare` are added, and the ones of `share` are removed, so that the
+    /// container shares them with the host. Namespaces are given by their OCI name, e.g. `network`.
+    /// The mount namespace cannot be shared, as the container mounts rely on it.
+    ///
+    /// It returns the namespaces of `share` which were actually removed, as the other ones
+    /// were already shared with the host.
+    pub fn apply_overrides(
+        &mut self,
+        unshare: &[String],
+        share: &[String],
+    ) -> crate::Result<Vec<String>> {
+        if let Some(name) = unshare.iter().find(|name| share.contains(name)) {
+            return Err(Error::InvalidNamespaceOverride(format!(
+                "namespace `{}` cannot be both shared and unshared",
+                name
+            )));
+        }
+
+        for name in unshare {
+            let namespace = Self::from_name(name)?;
+            if !self.vec.contains(&namespace) {
+                self.vec.push(namespace);
+            }
+        }
+
+        let mut shared = vec![];
+        for name in share {
+            let namespace = Self::from_name(name)?;
+            if namespace == Namespace::Mount {
+                return Err(Error::InvalidNamespaceOverride(String::from(
+                    "the mount namespace is required by the container and cannot be shared",
+                )));
+            }
+            if self.vec.contains(&namespace) {
+                self.vec.retain(|n| n != &namespace);
+                shared.push(name.clone());
+            }
+        }
+
+        Ok(shared)
+    }
+
+    /// Convert the OCI name of a supported namespace, e.g. `network`, to an `unshare::Namespace`.
+    fn from_name(name: &str) -> crate::Result<Namespace> {
+        match SUPPORTED_NAMESPACES.iter().find(|(n, _)| *n == name) {
+            Some((_, namespace)) => Self::from_oci_namespace(*namespace),
+            None => Err(Error::InvalidNamespaceOverride(format!(
+                "unsupported namespace `{}`, expected one of {}",
+                name,
+                SUPPORTED_NAMESPACES
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
     /// Convert an `oci_spec::runtime::LinuxNamespaceType` to an `unshare::Namespace`
     /// It returns an error if the namespace is invalid, or if it does not match any pattern.
     #[allow(unreachable_patterns)]
@@ -44,3 +115,90 @@ impl From<&Option<Vec<LinuxNamespace>>> for Namespaces {
         Self { vec }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn apply(
+        spec: Vec<Namespace>,
+        unshare: &[&str],
+        share: &[&str],
+    ) -> crate::Result<(Vec<Namespace>, Vec<String>)> {
+        let mut namespaces = Namespaces { vec: spec };
+        let shared = namespaces.apply_overrides(&names(unshare), &names(share))?;
+        Ok((namespaces.vec, shared))
+    }
+
+    #[test]
+    fn no_overrides() {
+        let spec = vec![Namespace::Mount, Namespace::Pid];
+
+        assert_eq!(apply(spec.clone(), &[], &[]).unwrap(), (spec, vec![]));
+    }
+
+    #[test]
+    fn unshare() {
+        let (namespaces, shared) =
+            apply(vec![Namespace::Mount], &["network", "uts", "mount"], &[]).unwrap();
+
+        assert_eq!(
+            namespaces,
+            [Namespace::Mount, Namespace::Net, Namespace::Uts]
+        );
+        assert!(shared.is_empty());
+    }
+
+    #[test]
+    fn share() {
+        let spec = vec![Namespace::Mount, Namespace::Pid, Namespace::Net];
+        let (namespaces, shared) = apply(spec, &[], &["network", "pid"]).unwrap();
+
+        assert_eq!(namespaces, [Namespace::Mount]);
+        assert_eq!(shared, ["network", "pid"]);
+    }
+
+    #[test]
+    fn share_a_namespace_not_requested() {
+        let (namespaces, shared) = apply(vec![Namespace::Mount], &[], &["pid"]).unwrap();
+
+        assert_eq!(namespaces, [Namespace::Mount]);
+        assert!(shared.is_empty());
+    }
+
+    #[test]
+    fn unshare_and_share() {
+        let spec = vec![Namespace::Mount, Namespace::Net];
+        let (namespaces, shared) = apply(spec, &["ipc", "cgroup"], &["network"]).unwrap();
+
+        assert_eq!(
+            namespaces,
+            [Namespace::Mount, Namespace::Ipc, Namespace::Cgroup]
+        );
+        assert_eq!(shared, ["network"]);
+    }
+
+    #[test]
+    fn invalid_overrides() {
+        for (unshare, share) in [
+            (&["pid"][..], &["pid"][..]),
+            (&[], &["mount"]),
+            (&["user"], &[]),
+            (&[], &["net"]),
+        ] {
+            assert!(
+                matches!(
+                    apply(vec![Namespace::Mount], unshare, share),
+                    Err(Error::InvalidNamespaceOverride(_))
+                ),
+                "unshare {:?} and share {:?} should be rejected",
+                unshare,
+                share
+            );
+        }
+    }
+}
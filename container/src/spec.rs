@@ -0,0 +1,191 @@
+use oci_spec::runtime::Spec;
+use serde_json::Value;
+
+/// The sections of the OCI specification our container acts on.
+/// Unknown fields are looked for at any depth under them.
+const STRICT_SECTIONS: [&str; 4] = ["process", "root", "mounts", "linux"];
+
+/// The fields of the OCI specification which only apply to other platforms than Linux.
+const OTHER_PLATFORM_FIELDS: [&str; 4] = ["windows", "solaris", "vm", "process.commandLine"];
+
+/// Get the first field of a raw OCI specification which only applies to another platform
+/// than Linux, e.g. a `windows` block.
+pub fn other_platform_field(raw: &Value) -> Option<&'static str> {
+    OTHER_PLATFORM_FIELDS.iter().copied().find(|field| {
+        field
+            .split('.')
+            .try_fold(raw, |value, key| value.get(key))
+            .is_some_and(|value| !value.is_null())
+    })
+}
+
+/// Get the fields of a raw OCI specification which were not consumed by its typed version.
+///
+/// The typed specification is serialized back, and its fields are compared to the raw ones:
+/// any field missing from it was unknown, and silently dropped when it was loaded.
+/// Fields are returned as dotted paths, e.g. `linux.namepaces` or `mounts[2].optoins`.
+pub fn unknown_fields(raw: &Value, spec: &Spec) -> serde_json::Result<Vec<String>> {
+    let typed = serde_json::to_value(spec)?;
+    let mut unknown = vec![];
+
+    if let Value::Object(raw) = raw {
+        for (key, value) in raw {
+            if value.is_null() {
+                continue;
+            }
+
+            match typed.get(key) {
+                None => unknown.push(key.clone()),
+                Some(typed) if STRICT_SECTIONS.contains(&key.as_str()) => {
+                    collect_unknown_fields(value, typed, key, &mut unknown)
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    Ok(unknown)
+}
+
+/// Recursively collect the fields of `raw` which are missing from `typed`.
+fn collect_unknown_fields(raw: &Value, typed: &Value, path: &str, unknown: &mut Vec<String>) {
+    match (raw, typed) {
+        (Value::Object(raw), _) => {
+            for (key, value) in raw {
+                // An explicit `null` is equivalent to an absent field, which is not serialized.
+                if value.is_null() {
+                    continue;
+                }
+
+                let field = format!("{}.{}", path, key);
+                match typed.get(key) {
+                    None => unknown.push(field),
+                    Some(typed) => collect_unknown_fields(value, typed, &field, unknown),
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(typed)) => {
+            for (index, (raw, typed)) in raw.iter().zip(typed).enumerate() {
+                collect_unknown_fields(raw, typed, &format!("{}[{}]", path, index), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn unknown(raw: Value) -> Vec<String> {
+        let spec: Spec = serde_json::from_value(raw.clone()).unwrap();
+        unknown_fields(&raw, &spec).unwrap()
+    }
+
+    #[test]
+    fn no_unknown_fields() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "process": {
+                "cwd": "/",
+                "args": ["sh"],
+                "user": { "uid": 0, "gid": 0 },
+                "env": ["PATH=/bin"]
+            },
+            "root": { "path": "rootfs", "readonly": true },
+            "hostname": "kaps",
+            "mounts": [{ "destination": "/proc", "type": "proc", "source": "proc" }],
+            "linux": { "namespaces": [{ "type": "pid" }, { "type": "mount" }] }
+        });
+
+        assert!(unknown(raw).is_empty());
+    }
+
+    #[test]
+    fn misspelled_fields() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "process": {
+                "cwd": "/",
+                "args": ["sh"],
+                "user": { "uid": 0, "gid": 0 },
+                "evn": ["PATH=/bin"]
+            },
+            "root": { "path": "rootfs", "readOnly": true },
+            "linux": { "namepaces": [{ "type": "pid" }] }
+        });
+
+        assert_eq!(
+            unknown(raw),
+            ["linux.namepaces", "process.evn", "root.readOnly"]
+        );
+    }
+
+    #[test]
+    fn extra_fields() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "extra": true,
+            "mounts": [
+                { "destination": "/proc", "type": "proc", "source": "proc" },
+                { "destination": "/tmp", "type": "tmpfs", "optoins": ["nosuid"] }
+            ]
+        });
+
+        assert_eq!(unknown(raw), ["extra", "mounts[1].optoins"]);
+    }
+
+    #[test]
+    fn null_fields_are_known() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "unknown": null,
+            "root": { "path": "rootfs", "readonly": null }
+        });
+
+        assert!(unknown(raw).is_empty());
+    }
+
+    #[test]
+    fn linux_specification() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "process": { "cwd": "/", "args": ["sh"] },
+            "linux": { "namespaces": [{ "type": "pid" }] }
+        });
+
+        assert_eq!(other_platform_field(&raw), None);
+    }
+
+    #[test]
+    fn windows_specification() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "windows": { "layerFolders": ["C:\\layers\\base"] }
+        });
+
+        assert_eq!(other_platform_field(&raw), Some("windows"));
+    }
+
+    #[test]
+    fn windows_command_line() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "process": { "cwd": "C:\\", "commandLine": "cmd.exe /c dir" }
+        });
+
+        assert_eq!(other_platform_field(&raw), Some("process.commandLine"));
+    }
+
+    #[test]
+    fn null_platform_fields_are_accepted() {
+        let raw = json!({
+            "ociVersion": "1.0.2",
+            "windows": null,
+            "process": { "cwd": "/", "args": ["sh"], "commandLine": null }
+        });
+
+        assert_eq!(other_platform_field(&raw), None);
+    }
+}
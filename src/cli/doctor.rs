@@ -0,0 +1,123 @@
+use crate::cli::Error;
+use crate::{Handler, Result};
+use clap::Args;
+use container::host;
+
+/// The binaries required on the host to run a container.
+///
+/// `mount` is not part of it: the container mounts are mounted once chrooted into the
+/// container, so `mount` is looked up in the rootfs of each bundle, not on the host.
+/// Only `umount` runs on the host, to clean up the mounts once the container has ended.
+const REQUIRED_BINARIES: [&str; 1] = ["umount"];
+
+/// Arguments for our `DoctorCommand`.
+///
+/// Example :
+///
+/// `kaps doctor`
+#[derive(Debug, Args)]
+pub struct DoctorCommand {}
+
+/// The result of a single host check.
+struct Check {
+    /// What is being checked.
+    name: String,
+    /// Whether the host passed the check.
+    passed: bool,
+    /// Whether containers cannot run at all if the check fails.
+    critical: bool,
+    /// How to fix the host if the check fails.
+    hint: &'static str,
+}
+
+impl Check {
+    fn new(name: &str, passed: bool, critical: bool, hint: &'static str) -> Self {
+        Check {
+            name: name.to_string(),
+            passed,
+            critical,
+            hint,
+        }
+    }
+}
+
+impl DoctorCommand {
+    /// Run every host check.
+    fn checks(&self) -> Vec<Check> {
+        let cgroup = host::cgroup_version();
+        let mut checks = vec![
+            Check::new(
+                "CAP_SYS_ADMIN capability",
+                host::has_cap_sys_admin(),
+                true,
+                "run kaps as root, e.g. with sudo",
+            ),
+            Check::new(
+                &format!(
+                    "cgroups ({})",
+                    cgroup.map_or(String::from("not mounted"), |version| version.to_string())
+                ),
+                cgroup.is_some(),
+                false,
+                "mount a cgroup2 filesystem on /sys/fs/cgroup",
+            ),
+            Check::new(
+                "cgroups writable",
+                host::cgroup_writable(),
+                false,
+                "remount /sys/fs/cgroup read-write",
+            ),
+            Check::new(
+                "overlayfs",
+                host::overlayfs_supported(),
+                false,
+                "load the overlay kernel module with `modprobe overlay`",
+            ),
+            Check::new(
+                "user namespaces",
+                host::user_namespaces_available(),
+                false,
+                "set the user.max_user_namespaces sysctl to a non-zero value",
+            ),
+        ];
+
+        for binary in REQUIRED_BINARIES {
+            checks.push(Check::new(
+                &format!("`{}` binary", binary),
+                host::find_binary(binary).is_some(),
+                true,
+                "install util-linux or busybox",
+            ));
+        }
+
+        checks
+    }
+}
+
+impl Handler for DoctorCommand {
+    fn handler(&self) -> Result<()> {
+        let checks = self.checks();
+
+        for check in &checks {
+            let status = match (check.passed, check.critical) {
+                (true, _) => "PASS",
+                (false, true) => "FAIL",
+                (false, false) => "WARN",
+            };
+            println!("[{}] {}", status, check.name);
+            if !check.passed {
+                println!("       hint: {}", check.hint);
+            }
+        }
+
+        let failed = checks
+            .iter()
+            .filter(|check| check.critical && !check.passed)
+            .count();
+        if failed > 0 {
+            return Err(Error::CriticalChecksFailed(failed));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use crate::cli::Error;
+use crate::{Handler, Result};
+use clap::Args;
+use container::features::Features;
+
+/// Arguments for our `FeaturesCommand`.
+///
+/// Example :
+///
+/// `kaps features`
+///
+/// It prints the OCI runtime features implemented by kaps, as JSON.
+#[derive(Debug, Args)]
+pub struct FeaturesCommand {}
+
+impl Handler for FeaturesCommand {
+    fn handler(&self) -> Result<()> {
+        let features = Features::detect();
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&features).map_err(Error::Json)?
+        );
+
+        Ok(())
+    }
+}
@@ -1,12 +1,25 @@
+mod doctor;
+mod features;
+mod report;
 mod run;
+mod version;
 
+use crate::cli::doctor::DoctorCommand;
+use crate::cli::features::FeaturesCommand;
 use crate::cli::run::RunCommand;
-use clap::{Parser, Subcommand};
+use crate::cli::version::VersionCommand;
+use clap::{ArgEnum, Parser, Subcommand};
+use std::fmt;
+
+pub use crate::cli::report::ErrorReport;
 
 /// CLI related errors
 #[derive(Debug)]
 pub enum Error {
+    Usage(clap::Error),
     Run(container::Error),
+    Json(serde_json::Error),
+    CriticalChecksFailed(usize),
 }
 
 impl From<container::Error> for Error {
@@ -15,9 +28,68 @@ impl From<container::Error> for Error {
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{}", e.to_string().trim_end()),
+            Error::Run(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "failed to serialize the output: {}", e),
+            Error::CriticalChecksFailed(failed) => {
+                write!(f, "{} critical host check(s) failed", failed)
+            }
+        }
+    }
+}
+
+impl Error {
+    /// A stable, machine-readable code identifying the error.
+    /// The codes of container errors are the ones of `container::Error::code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Usage(_) => "invalid_usage",
+            Error::Run(e) => e.code(),
+            Error::Json(_) => "output_failed",
+            Error::CriticalChecksFailed(_) => "critical_checks_failed",
+        }
+    }
+
+    /// The structured context of the error, e.g. the paths involved.
+    pub fn context(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Error::Run(e) => e.context(),
+            Error::Usage(_) | Error::Json(_) => vec![],
+            Error::CriticalChecksFailed(failed) => vec![("failed", failed.to_string())],
+        }
+    }
+
+    /// The exit code of kaps when the error occurs.
+    ///
+    /// - when the container exits with a non-zero code, kaps exits with the same code.
+    /// - `126`: the container process could not be spawned.
+    /// - `1`: `doctor` found that a critical host check failed.
+    /// - `2`: the command line arguments are invalid, as with `clap`.
+    /// - `125`: any other error of kaps itself, e.g. an invalid bundle or flag.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Run(container::Error::ContainerExit(code)) => *code,
+            Error::Run(container::Error::ContainerSpawnCommand(_)) => 126,
+            Error::CriticalChecksFailed(_) => 1,
+            _ => 125,
+        }
+    }
+}
+
 /// A common result type for our CLI.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The output formats supported by our commands.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
 /// `Handler` is a trait that should be implemented for each of our commands.
 ///
 /// It defines the contract & the input / output of a command execution.
@@ -27,17 +99,57 @@ pub trait Handler {
     /// Every command should take no argument, has it is built at runtime with these arguments.
     /// Also, a command must always return a `Result<()>`.
     fn handler(&self) -> crate::Result<()>;
+
+    /// The output format chosen for the command.
+    ///
+    /// When it is `Json`, a failure is reported as a JSON `ErrorReport`,
+    /// as with the global `--errors json` flag.
+    fn output(&self) -> OutputFormat {
+        OutputFormat::Human
+    }
 }
 
 #[derive(Parser, Debug)]
 #[clap(version, author)]
 pub struct Cli {
+    /// The format of the errors printed on failure. With `json`, a failure is reported
+    /// as a single JSON object on stderr, including invalid arguments.
+    #[clap(long, global = true, arg_enum, default_value = "human")]
+    pub(crate) errors: OutputFormat,
+
     /// Container bundle
     #[clap(subcommand)]
     pub(crate) command: Command,
 }
 
 impl Cli {
+    /// Get the format of the errors requested by some command line arguments, without
+    /// parsing them. It is used to report the arguments `clap` failed to parse.
+    pub fn errors_from_args<I: IntoIterator<Item = String>>(args: I) -> OutputFormat {
+        // Arguments after `--` are not options of kaps.
+        let args = args
+            .into_iter()
+            .take_while(|arg| arg != "--")
+            .collect::<Vec<String>>();
+
+        let mut errors = OutputFormat::Human;
+        for (index, arg) in args.iter().enumerate() {
+            let value = match arg.strip_prefix("--errors") {
+                Some("") => args.get(index + 1).map(String::as_str),
+                Some(value) => value.strip_prefix('='),
+                None => None,
+            };
+
+            match value {
+                Some("json") => errors = OutputFormat::Json,
+                Some("human") => errors = OutputFormat::Human,
+                _ => {}
+            }
+        }
+
+        errors
+    }
+
     /// Get the command used by the user.
     ///
     /// For example, if the user executes the command `run`,
@@ -45,7 +157,16 @@ impl Cli {
     /// execute it.
     pub fn command(self) -> Box<dyn Handler> {
         match self.command {
-            Command::Run(cmd) => Box::new(cmd),
+            Command::Run(mut cmd) => {
+                // The output of `run` only holds its failures, which `--errors` applies to.
+                if let OutputFormat::Json = self.errors {
+                    cmd.output = OutputFormat::Json;
+                }
+                Box::new(cmd)
+            }
+            Command::Version(cmd) => Box::new(cmd),
+            Command::Doctor(cmd) => Box::new(cmd),
+            Command::Features(cmd) => Box::new(cmd),
         }
     }
 }
@@ -64,4 +185,84 @@ impl Cli {
 pub enum Command {
     /// Run a container
     Run(RunCommand),
+    /// Show version, build and host information
+    Version(VersionCommand),
+    /// Check that the host can run containers
+    Doctor(DoctorCommand),
+    /// Show the OCI runtime features implemented by kaps
+    Features(FeaturesCommand),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn errors_from_args(args: &str) -> OutputFormat {
+        Cli::errors_from_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn errors_format_from_args() {
+        assert!(matches!(errors_from_args("kaps run"), OutputFormat::Human));
+        assert!(matches!(
+            errors_from_args("kaps --errors json run --bogus"),
+            OutputFormat::Json
+        ));
+        assert!(matches!(
+            errors_from_args("kaps run --bogus --errors=json"),
+            OutputFormat::Json
+        ));
+        assert!(matches!(
+            errors_from_args("kaps --errors json run --errors human"),
+            OutputFormat::Human
+        ));
+        // Arguments given to the container are not options of kaps.
+        assert!(matches!(
+            errors_from_args("kaps run -- --errors json"),
+            OutputFormat::Human
+        ));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let usage = Cli::try_parse_from(["kaps", "run", "--bogus"]).unwrap_err();
+
+        assert_eq!(Error::Usage(usage).exit_code(), 2);
+        assert_eq!(
+            Error::Run(container::Error::ContainerExit(42)).exit_code(),
+            42
+        );
+        assert_eq!(Error::CriticalChecksFailed(1).exit_code(), 1);
+        assert_eq!(
+            Error::Run(container::Error::InvalidTmpfs(String::from("size"))).exit_code(),
+            125
+        );
+    }
+
+    #[test]
+    fn usage_report() {
+        let usage =
+            Cli::try_parse_from(["kaps", "--errors", "json", "run", "--bogus"]).unwrap_err();
+        let report: serde_json::Value =
+            serde_json::from_str(&ErrorReport::from(&Error::Usage(usage)).to_json()).unwrap();
+
+        // The message is the one of clap, only its shape is pinned.
+        assert_eq!(report["code"], "invalid_usage");
+        assert!(report["message"].as_str().unwrap().contains("--bogus"));
+        assert_eq!(report.as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn container_report() {
+        let error = Error::Run(container::Error::RootfsOutsideBundle(
+            PathBuf::from("/srv/rootfs"),
+            PathBuf::from("/bundle"),
+        ));
+
+        assert_eq!(
+            ErrorReport::from(&error).to_json(),
+            r#"{"code":"rootfs_outside_bundle","message":"rootfs /srv/rootfs is outside of the bundle /bundle","details":{"bundle":"/bundle","rootfs":"/srv/rootfs"}}"#
+        );
+    }
 }
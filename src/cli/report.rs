@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::cli::Error;
+
+/// A machine-readable report of a failed command.
+///
+/// Its `code` is stable, so that tools wrapping kaps can branch on it
+/// rather than on the human readable `message`.
+#[derive(Serialize)]
+pub struct ErrorReport {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    details: BTreeMap<&'static str, String>,
+}
+
+impl ErrorReport {
+    /// Serialize the report to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("an error report only holds strings")
+    }
+}
+
+impl From<&Error> for ErrorReport {
+    fn from(error: &Error) -> Self {
+        ErrorReport {
+            code: error.code(),
+            message: error.to_string(),
+            details: error.context().into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn report(error: Error) -> (&'static str, String) {
+        (error.code(), ErrorReport::from(&error).to_json())
+    }
+
+    #[test]
+    fn container_error_with_details() {
+        let error = Error::Run(container::Error::RootfsNotFound(
+            PathBuf::from("rootfs"),
+            PathBuf::from("/bundle/rootfs"),
+        ));
+
+        assert_eq!(
+            report(error),
+            (
+                "rootfs_not_found",
+                String::from(
+                    r#"{"code":"rootfs_not_found","message":"rootfs rootfs (resolved to /bundle/rootfs) does not exist","details":{"configured":"rootfs","rootfs":"/bundle/rootfs"}}"#
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn container_error_without_details() {
+        let error = Error::Run(container::Error::InvalidEnv(String::from("FOO")));
+
+        assert_eq!(
+            report(error),
+            (
+                "invalid_env",
+                String::from(
+                    r#"{"code":"invalid_env","message":"invalid environment variable `FOO`, expected KEY=VALUE"}"#
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn container_exit() {
+        let error = Error::Run(container::Error::ContainerExit(3));
+
+        assert_eq!(
+            report(error),
+            (
+                "container_exited",
+                String::from(
+                    r#"{"code":"container_exited","message":"container exited with code 3","details":{"exit_code":"3"}}"#
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn cli_error() {
+        let error = Error::CriticalChecksFailed(2);
+
+        assert_eq!(
+            report(error),
+            (
+                "critical_checks_failed",
+                String::from(
+                    r#"{"code":"critical_checks_failed","message":"2 critical host check(s) failed","details":{"failed":"2"}}"#
+                )
+            )
+        );
+    }
+}
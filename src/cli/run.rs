@@ -1,3 +1,4 @@
+use crate::cli::OutputFormat;
 use crate::{Handler, Result};
 use clap::Args;
 use container::Container;
@@ -17,12 +18,106 @@ pub struct RunCommand {
     /// The bundle used by the container.
     #[clap(short, long)]
     bundle: String,
+
+    /// Refuse to run the container if its rootfs is outside of the bundle.
+    /// Use it when the bundle comes from an untrusted source.
+    #[clap(long)]
+    strict_rootfs: bool,
+
+    /// Refuse to run the container if its specification contains unknown fields,
+    /// e.g. a misspelled `namepaces` instead of `namespaces`.
+    #[clap(long)]
+    strict_spec: bool,
+
+    /// Do not mount the default `/dev`, `/proc` and `/sys` mounts into the container.
+    #[clap(long)]
+    no_default_mounts: bool,
+
+    /// Mount a tmpfs into the container, e.g. `/run:size=16m,mode=755`.
+    /// Can be repeated.
+    #[clap(long)]
+    tmpfs: Vec<String>,
+
+    /// Place the container into a namespace its specification does not request,
+    /// e.g. `network`. Can be repeated.
+    #[clap(long)]
+    unshare: Vec<String>,
+
+    /// Share a namespace requested by the specification with the host instead,
+    /// e.g. `network` for host networking. The `mount` namespace cannot be shared.
+    /// Can be repeated.
+    #[clap(long)]
+    share: Vec<String>,
+
+    /// Set an environment variable of the container, e.g. `KEY=VALUE`.
+    /// Can be repeated.
+    #[clap(short, long)]
+    env: Vec<String>,
+
+    /// Pass an environment variable of the host through to the container.
+    /// The host environment is never passed otherwise. Can be repeated.
+    #[clap(long)]
+    env_inherit: Vec<String>,
+
+    /// Pass the environment variables of the host whose name matches a glob pattern,
+    /// e.g. `LC_*`, through to the container. Can be repeated.
+    #[clap(long)]
+    env_inherit_pattern: Vec<String>,
+
+    /// Never display the value of an environment variable, e.g. a secret.
+    /// The container still receives its real value. Can be repeated.
+    #[clap(long)]
+    redact_env: Vec<String>,
+
+    /// The output format. With `json`, a failure is reported as a JSON object on stderr.
+    #[clap(short, long, arg_enum, default_value = "human")]
+    pub(crate) output: OutputFormat,
 }
 
 impl Handler for RunCommand {
     fn handler(&self) -> Result<()> {
         // Create a container by passing the bundle provided in arguments to it's constructor.
-        let container = Container::new(&self.bundle)?;
+        let mut container = Container::new(&self.bundle)?;
+
+        if self.strict_rootfs {
+            container.confine_rootfs()?;
+        }
+
+        if self.strict_spec {
+            container.check_spec_fields()?;
+        }
+
+        if self.no_default_mounts {
+            container.disable_default_mounts();
+        }
+
+        // Add the scratch tmpfs mounts requested on the command line.
+        for tmpfs in &self.tmpfs {
+            container.add_tmpfs(tmpfs)?;
+        }
+
+        let shared = container.override_namespaces(&self.unshare, &self.share)?;
+        // With a JSON output, stderr must only ever hold the JSON report of a failure.
+        if matches!(self.output, OutputFormat::Human)
+            && shared.iter().any(|namespace| namespace == "pid")
+        {
+            eprintln!("warning: sharing the host pid namespace lets the container see and signal host processes");
+        }
+
+        // Build the environment on top of the specification one: inherited variables first,
+        // so that explicitly set ones take precedence.
+        for pattern in &self.env_inherit_pattern {
+            container.inherit_env_matching(pattern);
+        }
+        for key in &self.env_inherit {
+            container.inherit_env(key);
+        }
+        for var in &self.env {
+            container.set_env(var)?;
+        }
+        for key in &self.redact_env {
+            container.redact_env(key);
+        }
 
         // Run the container
         // At the moment, we don't have a detached mode for the container,
@@ -31,4 +126,8 @@ impl Handler for RunCommand {
 
         Ok(())
     }
+
+    fn output(&self) -> OutputFormat {
+        self.output
+    }
 }
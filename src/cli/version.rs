@@ -0,0 +1,85 @@
+use crate::cli::{Error, OutputFormat};
+use crate::{Handler, Result};
+use clap::Args;
+use container::host;
+use serde::Serialize;
+
+/// Arguments for our `VersionCommand`.
+///
+/// Example :
+///
+/// `kaps version --output json`
+#[derive(Debug, Args)]
+pub struct VersionCommand {
+    /// The output format.
+    #[clap(short, long, arg_enum, default_value = "human")]
+    output: OutputFormat,
+}
+
+/// The build & runtime information reported by the `version` command.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Version {
+    version: &'static str,
+    git_commit: &'static str,
+    rustc: &'static str,
+    oci_version: &'static str,
+    features: Vec<&'static str>,
+    cgroup: Option<String>,
+}
+
+impl Version {
+    /// Gather the build information set by the build script, and detect the host cgroup version.
+    fn new() -> Self {
+        Version {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("KAPS_GIT_COMMIT"),
+            rustc: env!("KAPS_RUSTC_VERSION"),
+            oci_version: container::OCI_VERSION,
+            features: env!("KAPS_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect(),
+            cgroup: host::cgroup_version().map(|version| version.to_string()),
+        }
+    }
+}
+
+impl Handler for VersionCommand {
+    fn handler(&self) -> Result<()> {
+        let version = Version::new();
+
+        match self.output {
+            OutputFormat::Human => {
+                println!("Version:      {}", version.version);
+                println!("Git commit:   {}", version.git_commit);
+                println!("Rust:         {}", version.rustc);
+                println!("OCI version:  {}", version.oci_version);
+                println!(
+                    "Features:     {}",
+                    if version.features.is_empty() {
+                        String::from("none")
+                    } else {
+                        version.features.join(", ")
+                    }
+                );
+                println!(
+                    "Cgroup:       {}",
+                    version.cgroup.as_deref().unwrap_or("not detected")
+                );
+            }
+            OutputFormat::Json => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&version).map_err(Error::Json)?
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn output(&self) -> OutputFormat {
+        self.output
+    }
+}
@@ -1,13 +1,43 @@
 use clap::Parser;
 
-use crate::cli::{Cli, Handler, Result};
+use crate::cli::{Cli, Error, ErrorReport, Handler, OutputFormat, Result};
 
 mod cli;
 
-fn main() -> Result<()> {
-    let cli: Cli = Cli::parse();
+fn main() {
+    let cli: Cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Help and version requests are not failures, clap prints them as usual.
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => {
+            let args = std::env::args_os().map(|arg| arg.to_string_lossy().into_owned());
+            match Cli::errors_from_args(args) {
+                OutputFormat::Json => exit(OutputFormat::Json, Error::Usage(error)),
+                // Invalid arguments are printed by clap, along with the usage.
+                OutputFormat::Human => error.exit(),
+            }
+        }
+    };
+    let errors = cli.errors;
+    let command = cli.command();
 
-    cli.command().handler()?;
+    if let Err(error) = command.handler() {
+        let format = match (errors, command.output()) {
+            (OutputFormat::Human, OutputFormat::Human) => OutputFormat::Human,
+            _ => OutputFormat::Json,
+        };
+        exit(format, error);
+    }
+}
+
+/// Render a failure in the requested format, and exit with its code.
+/// Every failure is rendered here, in the format requested either globally
+/// or by the command itself.
+fn exit(format: OutputFormat, error: Error) -> ! {
+    match format {
+        OutputFormat::Json => eprintln!("{}", ErrorReport::from(&error).to_json()),
+        OutputFormat::Human => eprintln!("Error: {}", error),
+    }
 
-    Ok(())
+    std::process::exit(error.exit_code());
 }